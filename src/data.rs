@@ -0,0 +1,9 @@
+use crate::{IndexSet, IndexValueVec};
+
+/// A labelled dataset ready to be used for training or prediction.
+pub struct DataSet {
+    pub n_features: usize,
+    pub n_labels: usize,
+    pub feature_lists: Vec<IndexValueVec>,
+    pub label_sets: Vec<IndexSet>,
+}
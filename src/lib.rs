@@ -0,0 +1,16 @@
+#[macro_use]
+extern crate log;
+
+pub mod data;
+pub mod mat_util;
+pub mod model;
+pub mod util;
+
+/// Type used for indexing features and labels.
+pub type Index = u32;
+
+/// A set of indices, used to represent e.g. the set of labels associated with an example.
+pub type IndexSet = hashbrown::HashSet<Index>;
+
+/// A list of (index, value) pairs, used to represent e.g. a sparse feature vector.
+pub type IndexValueVec = Vec<(Index, f32)>;
@@ -0,0 +1,105 @@
+use crate::{Index, IndexValueVec};
+use itertools::Itertools;
+use sprs::{CsMat, CsMatView};
+
+/// Sparse matrix type used throughout this crate: rows are examples or labels, columns are
+/// features.
+pub type SparseMat = CsMat<f32>;
+
+/// A borrowed view into a [`SparseMat`].
+pub type SparseMatView<'a> = CsMatView<'a, f32>;
+
+/// Build a CSR sparse matrix from a list of sparse rows given as (index, value) pairs.
+///
+/// Each row is assumed to already be sorted by index.
+pub fn csrmat_from_index_value_pair_lists(rows: Vec<IndexValueVec>, n_cols: usize) -> SparseMat {
+    let mut indptr = Vec::with_capacity(rows.len() + 1);
+    let mut indices = Vec::new();
+    let mut data = Vec::new();
+    indptr.push(0);
+    for row in rows {
+        for (index, value) in row {
+            indices.push(index as usize);
+            data.push(value);
+        }
+        indptr.push(indices.len());
+    }
+    CsMat::new((indptr.len() - 1, n_cols), indptr, indices, data)
+}
+
+/// Extension methods for sparse (index, value) vectors, e.g. feature vectors or label centroids.
+pub trait IndexValueVecExt {
+    /// Scale the vector in-place so that its l2 norm is 1. No-op on an all-zero vector.
+    fn l2_normalize(&mut self);
+
+    /// Remove entries whose absolute value is below the given threshold.
+    fn prune_with_threshold(&mut self, threshold: f32);
+
+    /// Sort entries by index, as required before constructing a sparse matrix row from them.
+    fn sort_by_index(&mut self);
+}
+
+impl IndexValueVecExt for IndexValueVec {
+    fn l2_normalize(&mut self) {
+        let norm = self.iter().map(|&(_, v)| v * v).sum::<f32>().sqrt();
+        if norm > 0. {
+            self.iter_mut().for_each(|(_, v)| *v /= norm);
+        }
+    }
+
+    fn prune_with_threshold(&mut self, threshold: f32) {
+        self.retain(|&(_, v)| v.abs() >= threshold);
+    }
+
+    fn sort_by_index(&mut self) {
+        self.sort_by_key(|&(index, _)| index);
+    }
+}
+
+/// Extension methods for [`SparseMat`] used when slicing training examples or label clusters.
+pub trait SparseMatExt {
+    /// Build a new matrix made up of the given rows of this matrix, in the given order.
+    fn copy_outer_dims(&self, indices: &[usize]) -> SparseMat;
+
+    /// Remap column indices to a contiguous range, dropping columns that are all-zero.
+    ///
+    /// Returns the new matrix along with a vector mapping each new column index back to the
+    /// original one.
+    fn shrink_column_indices(self) -> (SparseMat, Vec<Index>);
+}
+
+impl SparseMatExt for SparseMat {
+    fn copy_outer_dims(&self, indices: &[usize]) -> SparseMat {
+        let rows = indices
+            .iter()
+            .map(|&i| self.outer_view(i).expect("Row index out of bound"))
+            .collect_vec();
+        let mut new_mat = CsMat::zero((rows.len(), self.cols()));
+        for row in rows {
+            new_mat = new_mat.append_outer_csvec(row.view());
+        }
+        new_mat
+    }
+
+    fn shrink_column_indices(self) -> (SparseMat, Vec<Index>) {
+        let mut old_to_new = hashbrown::HashMap::<usize, usize>::new();
+        let mut new_to_old = Vec::new();
+        for &col in self.indices() {
+            old_to_new.entry(col).or_insert_with(|| {
+                new_to_old.push(col as Index);
+                new_to_old.len() - 1
+            });
+        }
+
+        let (rows, _, indptr, indices, data) = (
+            self.rows(),
+            self.cols(),
+            self.indptr().raw_storage().to_vec(),
+            self.indices().to_vec(),
+            self.data().to_vec(),
+        );
+        let new_indices = indices.iter().map(|i| old_to_new[i]).collect_vec();
+        let new_mat = CsMat::new((rows, new_to_old.len()), indptr, new_indices, data);
+        (new_mat, new_to_old)
+    }
+}
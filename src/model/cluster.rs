@@ -0,0 +1,180 @@
+use crate::mat_util::*;
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+
+/// Hyper-parameters for the balanced spherical k-means clustering used to build each internal
+/// tree node's children.
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+pub struct HyperParam {
+    /// Number of clusters to partition a node's labels into at each split.
+    pub cluster_arity: usize,
+    pub n_iters: usize,
+}
+
+impl Default for HyperParam {
+    fn default() -> Self {
+        Self {
+            cluster_arity: 2,
+            n_iters: 10,
+        }
+    }
+}
+
+impl HyperParam {
+    /// Check if the hyper-parameter settings are valid.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.cluster_arity < 2 {
+            Err(format!(
+                "cluster_arity must be at least 2, but is {}",
+                self.cluster_arity
+            ))
+        } else if self.n_iters == 0 {
+            Err(format!("n_iters must be positive, but is {}", self.n_iters))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Partition the rows of `label_centroids` (one l2-normalized centroid per label) into
+    /// `cluster_arity` balanced clusters via spherical k-means, returning the row indices
+    /// belonging to each cluster.
+    ///
+    /// Returns a single cluster containing all rows if there are no more rows than
+    /// `cluster_arity`; no split is attempted in that case.
+    pub fn train(&self, label_centroids: &SparseMatView) -> Vec<Vec<usize>> {
+        let n = label_centroids.rows();
+        let k = self.cluster_arity;
+        if n <= k {
+            return vec![(0..n).collect_vec()];
+        }
+
+        // Initialize k seed centroids by taking k evenly-spaced rows of the (implicitly shuffled
+        // by insertion order) label set.
+        let mut centroids = (0..k)
+            .map(|i| dense_row(label_centroids, i * n / k))
+            .collect_vec();
+
+        let mut assignments = vec![0usize; n];
+        for _ in 0..self.n_iters {
+            // Assign every label to the centroid of highest cosine similarity.
+            let similarities = (0..n)
+                .map(|row| {
+                    let row_vec = dense_row(label_centroids, row);
+                    centroids
+                        .iter()
+                        .map(|centroid| dot(&row_vec, centroid))
+                        .collect_vec()
+                })
+                .collect_vec();
+
+            let mut new_assignments = (0..n)
+                .map(|row| argmax(&similarities[row]).expect("cluster_arity must be positive"))
+                .collect_vec();
+
+            // Enforce the balance constraint: repeatedly move the label with the smallest gain
+            // (similarity to its assigned centroid minus similarity to the next-best centroid)
+            // from the largest cluster to the smallest, until every cluster holds within +/-1 of
+            // n/k rows. Comparing largest against smallest (rather than stopping as soon as no
+            // cluster exceeds target_max) is what actually enforces the lower bound: a cluster
+            // can be under target_min even while no other cluster is over target_max.
+            let target_max = (n + k - 1) / k;
+            let target_min = n / k;
+            loop {
+                let mut cluster_sizes = vec![0usize; k];
+                for &c in &new_assignments {
+                    cluster_sizes[c] += 1;
+                }
+
+                let largest = (0..k).max_by_key(|&c| cluster_sizes[c]).unwrap();
+                let smallest = (0..k).min_by_key(|&c| cluster_sizes[c]).unwrap();
+                if cluster_sizes[largest] <= target_max && cluster_sizes[smallest] >= target_min {
+                    break;
+                }
+
+                let victim = (0..n)
+                    .filter(|&row| new_assignments[row] == largest)
+                    .min_by(|&a, &b| {
+                        gain(&similarities[a], largest).total_cmp(&gain(&similarities[b], largest))
+                    })
+                    .expect("largest cluster must be non-empty");
+
+                new_assignments[victim] = smallest;
+            }
+
+            let converged = new_assignments == assignments;
+            assignments = new_assignments;
+
+            // Recompute each centroid as the l2-normalized mean of its members.
+            centroids = (0..k)
+                .map(|c| {
+                    let mut mean = vec![0f32; label_centroids.cols()];
+                    let mut count = 0usize;
+                    for row in 0..n {
+                        if assignments[row] == c {
+                            let row_vec = dense_row(label_centroids, row);
+                            for (m, v) in mean.iter_mut().zip(row_vec.iter()) {
+                                *m += v;
+                            }
+                            count += 1;
+                        }
+                    }
+                    if count > 0 {
+                        l2_normalize_dense(&mut mean);
+                    }
+                    mean
+                })
+                .collect_vec();
+
+            if converged {
+                break;
+            }
+        }
+
+        let mut clusters = vec![Vec::new(); k];
+        for (row, &c) in assignments.iter().enumerate() {
+            clusters[c].push(row);
+        }
+        clusters.retain(|cluster| !cluster.is_empty());
+        clusters
+    }
+}
+
+fn dense_row(mat: &SparseMatView, row: usize) -> Vec<f32> {
+    let mut v = vec![0f32; mat.cols()];
+    if let Some(sparse_row) = mat.outer_view(row) {
+        for (col, &value) in sparse_row.iter() {
+            v[col] = value;
+        }
+    }
+    v
+}
+
+fn dot(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b.iter()).map(|(x, y)| x * y).sum()
+}
+
+fn l2_normalize_dense(v: &mut [f32]) {
+    let norm = v.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm > 0. {
+        v.iter_mut().for_each(|x| *x /= norm);
+    }
+}
+
+fn argmax(values: &[f32]) -> Option<usize> {
+    values
+        .iter()
+        .enumerate()
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(i, _)| i)
+}
+
+/// Similarity to the assigned cluster minus similarity to the next-best cluster.
+fn gain(similarities: &[f32], assigned: usize) -> f32 {
+    let best_other = similarities
+        .iter()
+        .enumerate()
+        .filter(|&(i, _)| i != assigned)
+        .map(|(_, &s)| s)
+        .fold(f32::NEG_INFINITY, f32::max);
+    similarities[assigned] - best_other
+}
@@ -0,0 +1,248 @@
+use crate::data::DataSet;
+use crate::{Index, IndexSet, IndexValueVec};
+use hashbrown::HashMap;
+use itertools::Itertools;
+
+/// The values of k at which Precision@k, nDCG@k and their propensity-scored variants are
+/// reported by [`evaluate`].
+pub const DEFAULT_TOP_K: &[usize] = &[1, 3, 5];
+
+/// Label propensity scores, used to compute propensity-scored precision/nDCG (PSP@k/PSnDCG@k),
+/// which down-weight popular labels so that correctly predicting rare (tail) labels counts for
+/// more.
+///
+/// Follows Jain et al., "Extreme Multi-label Loss Functions for Recommendation, Tagging, Ranking
+/// & Other Missing Label Applications" (KDD 2016): for a label `l` with training frequency `N_l`
+/// out of `N` examples, `p_l = 1 / (1 + C * exp(-A * log(N_l + B)))`, with
+/// `C = (log(N) - 1) * (B + 1)^A`.
+pub struct Propensity {
+    label_to_propensity: HashMap<Index, f32>,
+}
+
+impl Propensity {
+    /// Default value for the `a` constant, following the reference implementation.
+    pub const DEFAULT_A: f32 = 0.55;
+    /// Default value for the `b` constant, following the reference implementation.
+    pub const DEFAULT_B: f32 = 1.5;
+
+    /// Estimate propensity scores from the label sets of a training dataset, using the defaults
+    /// `a` = 0.55, `b` = 1.5.
+    pub fn new(dataset: &DataSet) -> Self {
+        Self::new_with_constants(dataset, Self::DEFAULT_A, Self::DEFAULT_B)
+    }
+
+    /// Estimate propensity scores from the label sets of a training dataset with custom `a`, `b`
+    /// constants.
+    pub fn new_with_constants(dataset: &DataSet, a: f32, b: f32) -> Self {
+        let n = dataset.label_sets.len() as f32;
+        let mut label_to_freq = HashMap::<Index, f32>::new();
+        for labels in &dataset.label_sets {
+            for &label in labels {
+                *label_to_freq.entry(label).or_default() += 1.;
+            }
+        }
+
+        let c = (n.ln() - 1.) * (b + 1.).powf(a);
+        let label_to_propensity = label_to_freq
+            .into_iter()
+            .map(|(label, freq)| (label, 1. / (1. + c * (-a * (freq + b).ln()).exp())))
+            .collect();
+
+        Self {
+            label_to_propensity,
+        }
+    }
+
+    /// Propensity-scored gain of correctly predicting the given label, `1 / p_l`. Labels unseen
+    /// during training are assigned a propensity of 1, i.e. no up-weighting.
+    fn gain(&self, label: Index) -> f32 {
+        1. / self.label_to_propensity.get(&label).copied().unwrap_or(1.)
+    }
+}
+
+/// Precision@k, nDCG@k and their propensity-scored counterparts, averaged over a set of
+/// predictions, for each k in a given list.
+#[derive(Debug, Clone)]
+pub struct EvalResult {
+    pub top_k: Vec<usize>,
+    pub precision: Vec<f32>,
+    pub ndcg: Vec<f32>,
+    pub psprecision: Vec<f32>,
+    pub psndcg: Vec<f32>,
+}
+
+/// Evaluate ranked predictions against ground-truth label sets, computing Precision@k, nDCG@k,
+/// PSP@k and PSnDCG@k for each k in `top_k`, averaged over all examples.
+///
+/// `predictions[i]` need not be pre-sorted by score; it is sorted by descending value here.
+pub fn evaluate(
+    predictions: &[IndexValueVec],
+    truths: &[IndexSet],
+    propensity: &Propensity,
+    top_k: &[usize],
+) -> EvalResult {
+    assert_eq!(predictions.len(), truths.len());
+    assert!(
+        !predictions.is_empty(),
+        "must evaluate at least one example"
+    );
+
+    let max_k = top_k.iter().cloned().max().unwrap_or(0);
+    let n = predictions.len() as f32;
+
+    let mut precision = vec![0f32; top_k.len()];
+    let mut ndcg = vec![0f32; top_k.len()];
+    let mut psprecision = vec![0f32; top_k.len()];
+    let mut psndcg = vec![0f32; top_k.len()];
+
+    for (prediction, truth) in predictions.iter().zip(truths.iter()) {
+        let ranked_labels = prediction
+            .iter()
+            .sorted_by(|(_, v1), (_, v2)| v2.partial_cmp(v1).expect("NaN prediction score"))
+            .map(|&(label, _)| label)
+            .take(max_k)
+            .collect_vec();
+
+        // Ideal per-position gains for a perfect top-k ranking, used as both the nDCG and PSnDCG
+        // normalizers' building blocks.
+        let mut truth_gains_desc = truth.iter().map(|&l| propensity.gain(l)).collect_vec();
+        truth_gains_desc.sort_by(|a, b| b.partial_cmp(a).expect("NaN propensity gain"));
+
+        for (i, &k) in top_k.iter().enumerate() {
+            let hits = ranked_labels
+                .iter()
+                .take(k)
+                .filter(|label| truth.contains(label))
+                .count();
+            precision[i] += hits as f32 / k as f32 / n;
+
+            let dcg: f32 = ranked_labels
+                .iter()
+                .take(k)
+                .enumerate()
+                .filter(|(_, label)| truth.contains(label))
+                .map(|(rank, _)| discount(rank))
+                .sum();
+            let idcg: f32 = (0..k.min(truth.len())).map(discount).sum();
+            if idcg > 0. {
+                ndcg[i] += dcg / idcg / n;
+            }
+
+            let ps_num: f32 = ranked_labels
+                .iter()
+                .take(k)
+                .filter(|label| truth.contains(label))
+                .map(|&label| propensity.gain(label))
+                .sum();
+            let ps_denom: f32 = truth_gains_desc.iter().take(k).sum();
+            if ps_denom > 0. {
+                psprecision[i] += ps_num / ps_denom / n;
+            }
+
+            let psdcg: f32 = ranked_labels
+                .iter()
+                .take(k)
+                .enumerate()
+                .filter(|(_, label)| truth.contains(label))
+                .map(|(rank, &label)| propensity.gain(label) * discount(rank))
+                .sum();
+            let ps_idcg: f32 = truth_gains_desc
+                .iter()
+                .take(k)
+                .enumerate()
+                .map(|(rank, &gain)| gain * discount(rank))
+                .sum();
+            if ps_idcg > 0. {
+                psndcg[i] += psdcg / ps_idcg / n;
+            }
+        }
+    }
+
+    EvalResult {
+        top_k: top_k.to_vec(),
+        precision,
+        ndcg,
+        psprecision,
+        psndcg,
+    }
+}
+
+/// nDCG's logarithmic discount for a 0-indexed rank: `1 / log2(rank + 2)`.
+#[inline]
+fn discount(rank: usize) -> f32 {
+    1. / (rank as f32 + 2.).log2()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::iter::FromIterator;
+
+    fn assert_close(actual: f32, expected: f32, what: &str) {
+        assert!(
+            (actual - expected).abs() < 1e-3,
+            "{} expected {} but got {}",
+            what,
+            expected,
+            actual
+        );
+    }
+
+    // Label 0 appears in 3 of 4 examples, label 1 in 1 of 4: label 1 is the rarer "tail" label
+    // and should get the larger propensity-scored gain.
+    fn test_dataset() -> DataSet {
+        DataSet {
+            n_features: 1,
+            n_labels: 4,
+            feature_lists: vec![vec![(0, 1.)]; 4],
+            label_sets: vec![
+                IndexSet::from_iter(vec![0]),
+                IndexSet::from_iter(vec![0]),
+                IndexSet::from_iter(vec![0]),
+                IndexSet::from_iter(vec![1]),
+            ],
+        }
+    }
+
+    #[test]
+    fn test_propensity_gain() {
+        let propensity = Propensity::new(&test_dataset());
+        // Hand-computed from p_l = 1 / (1 + C * exp(-A * log(N_l + B))) with A = 0.55, B = 1.5,
+        // C = (log(4) - 1) * 2.5^0.55 = 0.639419; gain = 1 / p_l = 1 + C * exp(...).
+        assert_close(propensity.gain(0), 1.279588, "gain(0)");
+        assert_close(propensity.gain(1), 1.386294, "gain(1)");
+    }
+
+    #[test]
+    fn test_evaluate() {
+        let propensity = Propensity::new(&test_dataset());
+        // Example 0: ranked predictions are [2, 1, 0, 3] (descending score); truth is {0, 1}, so
+        // the top prediction is a miss and the next two are hits.
+        // Example 1: ranked predictions are [0, 3, 1]; truth is {0}, a hit at rank 0.
+        let predictions = vec![
+            vec![(2, 0.9), (1, 0.8), (0, 0.7), (3, 0.1)],
+            vec![(0, 0.9), (3, 0.5), (1, 0.3)],
+        ];
+        let truths = vec![
+            IndexSet::from_iter(vec![0, 1]),
+            IndexSet::from_iter(vec![0]),
+        ];
+
+        let result = evaluate(&predictions, &truths, &propensity, &[1, 3]);
+
+        assert_eq!(result.top_k, vec![1, 3]);
+        // P@1 = (0/1 + 1/1) / 2; P@3 = (2/3 + 1/3) / 2
+        assert_close(result.precision[0], 0.5, "P@1");
+        assert_close(result.precision[1], 0.5, "P@3");
+        // nDCG@1 = (0 + 1) / 2; nDCG@3 averages 0.693426 (example 0) and 1.0 (example 1)
+        assert_close(result.ndcg[0], 0.5, "nDCG@1");
+        assert_close(result.ndcg[1], 0.846713, "nDCG@3");
+        // PSP@1 = (0 + 1) / 2; PSP@3 = (1 + 1) / 2, since both examples' top-3 predictions
+        // recover every truth label
+        assert_close(result.psprecision[0], 0.5, "PSP@1");
+        assert_close(result.psprecision[1], 1.0, "PSP@3");
+        // PSnDCG@1 = (0 + 1) / 2; PSnDCG@3 averages 0.690386 (example 0) and 1.0 (example 1)
+        assert_close(result.psndcg[0], 0.5, "PSnDCG@1");
+        assert_close(result.psndcg[1], 0.845193, "PSnDCG@3");
+    }
+}
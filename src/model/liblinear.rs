@@ -0,0 +1,169 @@
+use crate::mat_util::*;
+use crate::{Index, IndexValueVec};
+use hashbrown::HashMap;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// The per-label binary classification loss optimized by liblinear.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Loss {
+    /// L2-regularized L2-loss (squared-hinge) SVC, the objective of liblinear's
+    /// `L2R_L2LOSS_SVC_DUAL` solver. `HyperParam::train` does not run that solver; it uses the
+    /// approximation described in `train_one`. Scores are margins, not directly comparable
+    /// across trees.
+    Hinge,
+    /// L2-regularized logistic regression, the objective of liblinear's `L2R_LR_DUAL` solver.
+    /// `HyperParam::train` does not run that solver; it uses the approximation described in
+    /// `train_one`. Scores are mapped into (0, 1) by a sigmoid (see
+    /// `MultiLabelClassifier::transform_score`), so they can be averaged/multiplied across an
+    /// ensemble of trees.
+    Log,
+}
+
+impl Default for Loss {
+    fn default() -> Self {
+        Loss::Hinge
+    }
+}
+
+/// Hyper-parameters for training the per-label linear classifiers with liblinear.
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+pub struct HyperParam {
+    pub loss: Loss,
+    pub eps: f32,
+    pub c: f32,
+    pub weight_threshold: f32,
+    pub max_sample_size: usize,
+}
+
+impl Default for HyperParam {
+    fn default() -> Self {
+        Self {
+            loss: Loss::default(),
+            eps: 0.1,
+            c: 1.,
+            weight_threshold: 0.1,
+            max_sample_size: 32_000,
+        }
+    }
+}
+
+impl HyperParam {
+    /// Check if the hyper-parameter settings are valid.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.eps <= 0. {
+            Err(format!("eps must be positive, but is {}", self.eps))
+        } else if self.c <= 0. {
+            Err(format!("c must be positive, but is {}", self.c))
+        } else if self.weight_threshold < 0. {
+            Err(format!(
+                "weight_threshold must be non-negative, but is {}",
+                self.weight_threshold
+            ))
+        } else if self.max_sample_size == 0 {
+            Err(format!(
+                "max_sample_size must be positive, but is {}",
+                self.max_sample_size
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Scale down `c` for nodes with many more examples than `max_sample_size`, so that training
+    /// cost stays roughly bounded regardless of how large the dataset is.
+    pub fn adapt_to_sample_size(&self, n_examples: usize) -> Self {
+        let mut adapted = *self;
+        if n_examples > self.max_sample_size {
+            adapted.c *= self.max_sample_size as f32 / n_examples as f32;
+        }
+        adapted
+    }
+
+    /// Train one-vs-rest binary classifiers for each label over the given examples.
+    pub fn train(
+        &self,
+        feature_matrix: &SparseMatView,
+        label_to_example_indices: &[Vec<usize>],
+        index_to_feature: &[Index],
+        n_features: usize,
+    ) -> MultiLabelClassifier {
+        let weights = label_to_example_indices
+            .par_iter()
+            .map(|example_indices| {
+                self.train_one(feature_matrix, example_indices, index_to_feature)
+            })
+            .collect();
+
+        MultiLabelClassifier {
+            loss: self.loss,
+            weights,
+            n_features,
+        }
+    }
+
+    fn train_one(
+        &self,
+        feature_matrix: &SparseMatView,
+        positive_example_indices: &[usize],
+        index_to_feature: &[Index],
+    ) -> IndexValueVec {
+        // Placeholder for the actual liblinear dual-coordinate-descent solve: average, over all
+        // examples, each feature vector signed +1 for positive and -1 for negative examples as a
+        // simple linear separator, then prune small weights the same way the real solver's output
+        // is pruned. Each contribution is scaled by a per-loss factor: 1 for squared-hinge, and
+        // 1/2 for logistic (the magnitude of the logistic loss's derivative at a margin of 0).
+        let loss_scale = match self.loss {
+            Loss::Hinge => 1.,
+            Loss::Log => 0.5,
+        };
+        let positive: hashbrown::HashSet<usize> =
+            positive_example_indices.iter().cloned().collect();
+        let mut feature_to_weight = HashMap::<Index, f32>::new();
+        for row in 0..feature_matrix.rows() {
+            let sign = if positive.contains(&row) { 1. } else { -1. };
+            if let Some(vec) = feature_matrix.outer_view(row) {
+                for (col, &value) in vec.iter() {
+                    *feature_to_weight.entry(index_to_feature[col]).or_default() +=
+                        loss_scale * sign * value / feature_matrix.rows() as f32;
+                }
+            }
+        }
+
+        let mut weights = feature_to_weight.into_iter().collect_vec_sorted();
+        weights.prune_with_threshold(self.weight_threshold);
+        weights
+    }
+}
+
+trait CollectSorted {
+    fn collect_vec_sorted(self) -> IndexValueVec;
+}
+
+impl<I: Iterator<Item = (Index, f32)>> CollectSorted for I {
+    fn collect_vec_sorted(self) -> IndexValueVec {
+        let mut v: IndexValueVec = self.collect();
+        v.sort_by_index();
+        v
+    }
+}
+
+/// A set of per-label one-vs-rest linear classifiers sharing a feature space.
+#[derive(Serialize, Deserialize)]
+pub struct MultiLabelClassifier {
+    pub loss: Loss,
+    pub weights: Vec<IndexValueVec>,
+    pub n_features: usize,
+}
+
+impl MultiLabelClassifier {
+    /// Transform a raw dot-product score into the score this classifier's loss makes meaningful
+    /// for averaging/multiplying across an ensemble: a margin is passed through unchanged, while
+    /// a logistic-loss score is squashed into a probability in (0, 1) with a sigmoid.
+    pub fn transform_score(&self, raw_score: f32) -> f32 {
+        match self.loss {
+            Loss::Hinge => raw_score,
+            Loss::Log => 1. / (1. + (-raw_score).exp()),
+        }
+    }
+}
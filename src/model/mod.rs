@@ -0,0 +1,33 @@
+use crate::Index;
+use serde::{Deserialize, Serialize};
+
+pub mod cluster;
+pub mod eval;
+pub mod liblinear;
+pub mod train;
+
+/// A trained parabel model: a forest of independently trained trees.
+#[derive(Serialize, Deserialize)]
+pub struct Model {
+    pub trees: Vec<Tree>,
+    pub n_features: usize,
+}
+
+/// A single tree in the forest.
+#[derive(Serialize, Deserialize)]
+pub struct Tree {
+    pub root: TreeNode,
+}
+
+/// A node of a [`Tree`].
+#[derive(Serialize, Deserialize)]
+pub enum TreeNode {
+    BranchNode {
+        classifier: liblinear::MultiLabelClassifier,
+        children: Vec<TreeNode>,
+    },
+    LeafNode {
+        classifier: liblinear::MultiLabelClassifier,
+        labels: Vec<Index>,
+    },
+}
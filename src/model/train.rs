@@ -15,7 +15,7 @@ use std::sync::{Arc, Mutex};
 #[derive(Copy, Clone, Debug, Serialize, Deserialize)]
 pub struct HyperParam {
     pub n_trees: usize,
-    pub min_branch_size: usize,
+    pub max_leaf_size: usize,
     pub max_depth: usize,
     pub centroid_threshold: f32,
     pub linear: liblinear::HyperParam,
@@ -26,7 +26,7 @@ impl Default for HyperParam {
     fn default() -> Self {
         Self {
             n_trees: 3,
-            min_branch_size: 100,
+            max_leaf_size: 100,
             max_depth: 20,
             centroid_threshold: 0.,
             linear: liblinear::HyperParam::default(),
@@ -40,10 +40,10 @@ impl HyperParam {
     pub fn validate(&self) -> Result<(), String> {
         if self.n_trees == 0 {
             Err(format!("n_trees must be positive, but is {}", self.n_trees))
-        } else if self.min_branch_size <= 1 {
+        } else if self.max_leaf_size == 0 {
             Err(format!(
-                "min_branch_size must be greater than 1, but is {}",
-                self.min_branch_size
+                "max_leaf_size must be positive, but is {}",
+                self.max_leaf_size
             ))
         } else if self.centroid_threshold < 0. {
             Err(format!(
@@ -69,19 +69,38 @@ impl HyperParam {
     /// Here we take ownership of the dataset object to perform necessary prepossessing. One can
     /// choose to clone a dataset before passing it in to avoid losing the original data.
     pub fn train(&self, dataset: DataSet) -> Model {
-        self.validate().unwrap();
+        let hyper_params = vec![*self; self.n_trees];
+        Self::train_ensemble(dataset, &hyper_params)
+    }
+
+    /// Train a heterogeneous forest, in which each tree is grown from its own hyper-parameters
+    /// (e.g. mixing shallow wide trees with deep balanced-binary ones), and merge the resulting
+    /// trees into a single model.
+    ///
+    /// Here we take ownership of the dataset object to perform necessary prepossessing. One can
+    /// choose to clone a dataset before passing it in to avoid losing the original data.
+    pub fn train_ensemble(dataset: DataSet, hyper_params: &[HyperParam]) -> Model {
+        assert!(!hyper_params.is_empty(), "hyper_params must be non-empty");
+        for hyper_param in hyper_params {
+            hyper_param.validate().unwrap();
+        }
         let n_features = dataset.n_features;
 
-        info!("Training Parabel model with hyper-parameters {:?}", self);
+        info!(
+            "Training Parabel forest of {} trees with hyper-parameters {:?}",
+            hyper_params.len(),
+            hyper_params
+        );
         let start_t = time::precise_time_s();
 
         info!("Initializing tree trainer");
-        let trainer = TreeTrainer::initialize(dataset, *self);
+        let trainer = TreeTrainer::initialize(dataset);
+        trainer.set_progress_total((trainer.all_labels_len() * hyper_params.len()) as u64);
 
         info!("Start training forest");
-        let trees: Vec<_> = (0..self.n_trees)
+        let trees: Vec<_> = hyper_params
             .into_par_iter()
-            .map(|_| trainer.train())
+            .map(|hyper_param| trainer.train(hyper_param))
             .collect();
 
         info!(
@@ -94,114 +113,131 @@ impl HyperParam {
 
 struct TreeTrainer {
     all_examples: Arc<TrainingExamples>,
-    all_labels: Arc<LabelCluster>,
-    hyper_param: HyperParam,
+    base_all_labels: Arc<LabelCluster>,
     progress_bar: Mutex<ProgressBar>,
 }
 
 impl TreeTrainer {
-    /// Initialize a reusable tree trainer with the dataset and hyper-parameters.
+    /// Initialize a reusable tree trainer with the dataset, shared across all trees in the
+    /// forest regardless of their individual hyper-parameters.
     ///
     /// Dataset is assumed to be well-formed.
-    fn initialize(mut dataset: DataSet, hyper_param: HyperParam) -> Self {
+    fn initialize(mut dataset: DataSet) -> Self {
         assert_eq!(dataset.feature_lists.len(), dataset.label_sets.len());
         // l2-normalize all examples in the dataset
         dataset
             .feature_lists
             .par_iter_mut()
             .for_each(|v| v.l2_normalize());
-        // Initialize label clusters
-        let all_labels = Arc::new(LabelCluster::new_from_dataset(
-            &dataset,
-            hyper_param.centroid_threshold,
-        ));
+        // Initialize label clusters without pruning; each tree prunes down to its own
+        // `centroid_threshold` when its training starts.
+        let base_all_labels = Arc::new(LabelCluster::new_from_dataset(&dataset, 0.));
 
         // Initialize examples set
         let all_examples = Arc::new(TrainingExamples::new_from_dataset(dataset));
 
-        let progress_bar = Mutex::new(create_progress_bar(
-            (all_labels.len() * hyper_param.n_trees) as u64,
-        ));
+        let progress_bar = Mutex::new(create_progress_bar(0));
 
         Self {
             all_examples,
-            all_labels,
-            hyper_param,
+            base_all_labels,
             progress_bar,
         }
     }
 
     #[inline]
-    fn classifier_hyper_param(&self, n_examples: usize) -> liblinear::HyperParam {
-        self.hyper_param
-            .linear
-            .adapt_to_sample_size(n_examples, self.all_examples.len())
+    fn all_labels_len(&self) -> usize {
+        self.base_all_labels.len()
+    }
+
+    #[inline]
+    fn set_progress_total(&self, total: u64) {
+        self.progress_bar
+            .lock()
+            .expect("Failed to lock progress bar")
+            .total = total;
+    }
+
+    #[inline]
+    fn classifier_hyper_param(
+        &self,
+        hyper_param: &HyperParam,
+        n_examples: usize,
+    ) -> liblinear::HyperParam {
+        hyper_param.linear.adapt_to_sample_size(n_examples)
     }
 
-    fn train(&self) -> Tree {
+    fn train(&self, hyper_param: &HyperParam) -> Tree {
+        let all_labels = Arc::new(
+            self.base_all_labels
+                .pruned_with_threshold(hyper_param.centroid_threshold),
+        );
         Tree {
-            root: self.train_subtree(1, self.all_examples.clone(), self.all_labels.clone()),
+            root: self.train_subtree(hyper_param, 1, self.all_examples.clone(), all_labels),
         }
     }
 
     fn train_subtree(
         &self,
+        hyper_param: &HyperParam,
         depth: usize,
         examples: Arc<TrainingExamples>,
         label_cluster: Arc<LabelCluster>,
     ) -> TreeNode {
-        // If we haven't reached depth limit, have enough labels for further branching,
-        // and also successfully performed clustering, then recursively branch and train subtrees
-        if depth < self.hyper_param.max_depth
-            && label_cluster.len() >= self.hyper_param.min_branch_size
-        {
-            if let Some(label_clusters) = label_cluster.split(self.hyper_param.cluster) {
-                assert!(label_clusters.len() > 1);
-                self.progress_bar
-                    .lock()
-                    .expect("Failed to lock progress bar")
-                    .total += label_clusters.len() as u64;
-
-                drop(label_cluster); // No longer needed
-
-                let example_index_lists = label_clusters
-                    .par_iter()
-                    .map(|cluster| examples.find_examples_with_labels(&cluster.labels))
-                    .collect::<Vec<_>>();
-
-                let (children, classifier) = rayon::join(
-                    {
-                        let examples = examples.clone();
-                        || {
-                            self.train_child_nodes(
-                                depth,
-                                examples,
-                                label_clusters,
-                                &example_index_lists,
-                            )
-                        }
-                    },
+        // If we haven't reached depth limit and the cluster is too large for a leaf, branch and
+        // train subtrees; fall back to an arbitrary balanced partition if clustering degenerates.
+        if depth < hyper_param.max_depth && label_cluster.len() > hyper_param.max_leaf_size {
+            let label_clusters = label_cluster.split(hyper_param.cluster).unwrap_or_else(|| {
+                label_cluster.split_arbitrarily(hyper_param.cluster.cluster_arity)
+            });
+            assert!(label_clusters.len() > 1);
+            self.progress_bar
+                .lock()
+                .expect("Failed to lock progress bar")
+                .total += label_clusters.len() as u64;
+
+            drop(label_cluster); // No longer needed
+
+            let example_index_lists = label_clusters
+                .par_iter()
+                .map(|cluster| examples.find_examples_with_labels(&cluster.labels))
+                .collect::<Vec<_>>();
+
+            let (children, classifier) = rayon::join(
+                {
+                    let examples = examples.clone();
                     || {
-                        self.train_classifier(
-                            examples, // NB: the Arc "examples" is moved into this closure
+                        self.train_child_nodes(
+                            hyper_param,
+                            depth,
+                            examples,
+                            label_clusters,
                             &example_index_lists,
                         )
-                    },
-                );
-
-                return TreeNode::BranchNode {
-                    classifier,
-                    children,
-                };
-            }
+                    }
+                },
+                || {
+                    self.train_classifier(
+                        hyper_param,
+                        examples, // NB: the Arc "examples" is moved into this closure
+                        &example_index_lists,
+                    )
+                },
+            );
+
+            return TreeNode::BranchNode {
+                classifier,
+                children,
+            };
         }
 
         // Otherwise stop branching and train a leaf node
-        self.train_leaf_node(examples, &label_cluster.labels)
+        self.train_leaf_node(hyper_param, examples, &label_cluster.labels)
     }
 
     fn train_child_nodes(
         &self,
+        hyper_param: &HyperParam,
         depth: usize,
         examples: Arc<TrainingExamples>,
         label_clusters: Vec<LabelCluster>,
@@ -217,6 +253,7 @@ impl TreeTrainer {
                 let cluster_examples = examples.take_examples_by_indices(example_indices);
                 drop(examples); // No longer needed
                 self.train_subtree(
+                    hyper_param,
                     depth + 1,
                     Arc::new(cluster_examples),
                     Arc::new(label_cluster),
@@ -225,13 +262,18 @@ impl TreeTrainer {
             .collect()
     }
 
-    fn train_leaf_node(&self, examples: Arc<TrainingExamples>, leaf_labels: &[Index]) -> TreeNode {
+    fn train_leaf_node(
+        &self,
+        hyper_param: &HyperParam,
+        examples: Arc<TrainingExamples>,
+        leaf_labels: &[Index],
+    ) -> TreeNode {
         let classifier = {
             let example_index_lists = leaf_labels
                 .par_iter()
                 .map(|&label| examples.find_examples_with_label(label))
                 .collect::<Vec<_>>();
-            self.train_classifier(examples, &example_index_lists)
+            self.train_classifier(hyper_param, examples, &example_index_lists)
         };
         TreeNode::LeafNode {
             classifier,
@@ -241,15 +283,18 @@ impl TreeTrainer {
 
     fn train_classifier(
         &self,
+        hyper_param: &HyperParam,
         examples: Arc<TrainingExamples>,
         label_to_example_indices: &[Vec<usize>],
     ) -> liblinear::MultiLabelClassifier {
-        let classifier = self.classifier_hyper_param(examples.len()).train(
-            &examples.feature_matrix.view(),
-            label_to_example_indices,
-            &examples.index_to_feature,
-            self.all_examples.n_features(),
-        );
+        let classifier = self
+            .classifier_hyper_param(hyper_param, examples.len())
+            .train(
+                &examples.feature_matrix.view(),
+                label_to_example_indices,
+                &examples.index_to_feature,
+                self.all_examples.n_features(),
+            );
 
         self.progress_bar
             .lock()
@@ -417,6 +462,30 @@ impl LabelCluster {
             .unzip()
     }
 
+    /// Re-prune the (unpruned) label centroids down to the given threshold, without recomputing
+    /// them from the dataset. Lets a shared, unpruned base `LabelCluster` be reused by trees that
+    /// each want their own `centroid_threshold`.
+    fn pruned_with_threshold(&self, threshold: f32) -> Self {
+        let rows = (0..self.feature_matrix.rows())
+            .map(|row| {
+                let mut v: IndexValueVec = self
+                    .feature_matrix
+                    .outer_view(row)
+                    .map(|sparse_row| {
+                        sparse_row
+                            .iter()
+                            .map(|(c, &value)| (c as Index, value))
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                v.prune_with_threshold(threshold);
+                v
+            })
+            .collect_vec();
+        let feature_matrix = csrmat_from_index_value_pair_lists(rows, self.feature_matrix.cols());
+        Self::new(self.labels.clone(), feature_matrix)
+    }
+
     fn take_labels_by_indices(&self, indices: &[usize]) -> Self {
         let new_labels = indices.iter().map(|&i| self.labels[i]).collect_vec();
         let (new_feature_matrix, _) = self
@@ -445,6 +514,23 @@ impl LabelCluster {
             None
         }
     }
+
+    /// Partition the label set into (up to) `arity` arbitrary, evenly-sized chunks, ignoring
+    /// feature similarity entirely.
+    ///
+    /// Used as a fallback when `split` degenerates (e.g. identical label centroids) but the
+    /// cluster is still too large to be a leaf, so that `max_leaf_size` is honored wherever
+    /// `max_depth` still allows branching.
+    fn split_arbitrarily(&self, arity: usize) -> Vec<Self> {
+        let n = self.len();
+        let n_chunks = arity.min(n);
+        let chunk_size = (n + n_chunks - 1) / n_chunks;
+        (0..n)
+            .collect_vec()
+            .chunks(chunk_size)
+            .map(|indices| self.take_labels_by_indices(indices))
+            .collect_vec()
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,29 @@
+use indicatif::{ProgressBar as PBar, ProgressStyle};
+use std::ops::{Deref, DerefMut};
+
+/// Thin wrapper around `indicatif::ProgressBar` with the styling used across this crate.
+pub struct ProgressBar(PBar);
+
+impl Deref for ProgressBar {
+    type Target = PBar;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for ProgressBar {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+/// Create a progress bar with the given total count, styled consistently for this crate.
+pub fn create_progress_bar(total: u64) -> ProgressBar {
+    let pb = PBar::new(total);
+    pb.set_style(
+        ProgressStyle::default_bar()
+            .template("{elapsed_precise} {bar:40.cyan/blue} {pos:>7}/{len:7} {msg}"),
+    );
+    ProgressBar(pb)
+}